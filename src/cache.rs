@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+use crate::time::now_secs;
+
+#[cfg(feature = "redis-cache")]
+use redis::AsyncCommands;
+
+/// Resolved lyrics cache, backed by an in-memory map by default or Redis
+/// when the `redis-cache` feature is enabled, so multiple proxy instances
+/// can share a cache.
+pub enum Cache {
+    Memory(MemoryCache),
+    #[cfg(feature = "redis-cache")]
+    Redis(RedisCache),
+}
+
+impl Cache {
+    pub fn memory() -> Self {
+        Cache::Memory(MemoryCache::default())
+    }
+
+    #[cfg(feature = "redis-cache")]
+    pub fn redis(url: &str) -> anyhow::Result<Self> {
+        Ok(Cache::Redis(RedisCache::new(url)?))
+    }
+
+    /// Looks up `track_id`, returning the cached value alongside how many
+    /// seconds it has left before expiring — callers need that to report an
+    /// accurate `Cache-Control: max-age` on a hit instead of the full TTL.
+    pub async fn get(&self, track_id: &str) -> Option<(Value, u64)> {
+        match self {
+            Cache::Memory(cache) => cache.get(track_id),
+            #[cfg(feature = "redis-cache")]
+            Cache::Redis(cache) => cache.get(track_id).await,
+        }
+    }
+
+    pub async fn set(&self, track_id: &str, lyrics: Value, ttl_secs: u64) {
+        match self {
+            Cache::Memory(cache) => cache.set(track_id, lyrics, ttl_secs),
+            #[cfg(feature = "redis-cache")]
+            Cache::Redis(cache) => cache.set(track_id, lyrics, ttl_secs).await,
+        }
+    }
+}
+
+struct Entry {
+    value: Value,
+    expires_at: u64,
+}
+
+#[derive(Default)]
+pub struct MemoryCache {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl MemoryCache {
+    fn get(&self, track_id: &str) -> Option<(Value, u64)> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(track_id)?;
+
+        let now = now_secs();
+        if entry.expires_at <= now {
+            return None;
+        }
+
+        Some((entry.value.clone(), entry.expires_at - now))
+    }
+
+    fn set(&self, track_id: &str, value: Value, ttl_secs: u64) {
+        let mut entries = self.entries.lock().unwrap();
+
+        // Sweep expired entries on every write so a long-running instance
+        // doesn't accumulate one entry per distinct track_id forever; `get`
+        // alone only ever skips expired entries, it never removes them.
+        let now = now_secs();
+        entries.retain(|_, entry| entry.expires_at > now);
+
+        entries.insert(
+            track_id.to_string(),
+            Entry {
+                value,
+                expires_at: now + ttl_secs,
+            },
+        );
+    }
+}
+
+#[cfg(feature = "redis-cache")]
+pub struct RedisCache {
+    client: redis::Client,
+}
+
+#[cfg(feature = "redis-cache")]
+impl RedisCache {
+    fn new(url: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: redis::Client::open(url)?,
+        })
+    }
+
+    async fn get(&self, track_id: &str) -> Option<(Value, u64)> {
+        let mut conn = self.client.get_multiplexed_async_connection().await.ok()?;
+        let key = cache_key(track_id);
+
+        let raw: Option<String> = conn.get(&key).await.ok()?;
+        let value = raw.and_then(|raw| serde_json::from_str(&raw).ok())?;
+
+        // Redis expires the key itself, so the remaining TTL has to be asked
+        // for separately; a missing/expired key reports -2 or -1, treat both
+        // as no time left rather than failing the lookup.
+        let ttl_remaining_secs: i64 = conn.ttl(&key).await.unwrap_or(0);
+
+        Some((value, ttl_remaining_secs.max(0) as u64))
+    }
+
+    async fn set(&self, track_id: &str, value: Value, ttl_secs: u64) {
+        let Ok(mut conn) = self.client.get_multiplexed_async_connection().await else {
+            return;
+        };
+        let _: Result<(), _> = conn
+            .set_ex(cache_key(track_id), value.to_string(), ttl_secs)
+            .await;
+    }
+}
+
+#[cfg(feature = "redis-cache")]
+fn cache_key(track_id: &str) -> String {
+    format!("lyrics:{}", track_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn set_then_get_returns_the_value_and_remaining_ttl() {
+        let cache = MemoryCache::default();
+        cache.set("track", json!({"lines": []}), 300);
+
+        let (value, ttl_remaining_secs) = cache.get("track").unwrap();
+        assert_eq!(value, json!({"lines": []}));
+        assert!(ttl_remaining_secs <= 300);
+    }
+
+    #[test]
+    fn get_returns_none_for_a_missing_entry() {
+        let cache = MemoryCache::default();
+        assert!(cache.get("missing").is_none());
+    }
+
+    #[test]
+    fn get_returns_none_for_an_expired_entry() {
+        let cache = MemoryCache::default();
+        cache.set("track", json!({}), 0);
+
+        assert!(cache.get("track").is_none());
+    }
+
+    #[test]
+    fn set_sweeps_expired_entries_on_write() {
+        let cache = MemoryCache::default();
+        cache.set("stale", json!({}), 0);
+        cache.set("fresh", json!({}), 300);
+
+        let entries = cache.entries.lock().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries.contains_key("fresh"));
+    }
+}
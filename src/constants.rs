@@ -0,0 +1,10 @@
+pub const TOKEN_URL: &str = "https://open.spotify.com/get_access_token";
+pub const LYRICS_URL: &str = "https://spclient.wg.spotify.com/color-lyrics/v2/track/";
+pub const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/121.0.0.0 Safari/537.36";
+
+pub const OAUTH_AUTHORIZE_URL: &str = "https://accounts.spotify.com/authorize";
+pub const OAUTH_TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
+pub const OAUTH_SCOPES: &str = "user-read-private user-read-email streaming";
+pub const OAUTH_CREDENTIAL_KEY: &str = "oauth";
+
+pub const DEFAULT_CACHE_TTL_SECS: u64 = 300;
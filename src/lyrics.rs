@@ -0,0 +1,205 @@
+use serde_json::Value;
+
+/// Output format requested for a `/lyrics/:track_id` response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Lrc,
+    Vtt,
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Lrc => "text/plain; charset=utf-8",
+            Format::Vtt => "text/vtt; charset=utf-8",
+        }
+    }
+}
+
+/// Resolves the requested format from the `?format=` query param, falling
+/// back to the `Accept` header, and defaulting to the raw JSON payload.
+pub fn parse_format(query: Option<&str>, accept: Option<&str>) -> Format {
+    if let Some(format) = query {
+        match format.to_ascii_lowercase().as_str() {
+            "lrc" => return Format::Lrc,
+            "vtt" => return Format::Vtt,
+            "json" => return Format::Json,
+            _ => {}
+        }
+    }
+
+    if let Some(accept) = accept {
+        if accept.contains("text/vtt") {
+            return Format::Vtt;
+        }
+        if accept.contains("x-lrc") {
+            return Format::Lrc;
+        }
+    }
+
+    Format::Json
+}
+
+struct Line {
+    start_ms: u64,
+    words: String,
+}
+
+fn parse_lines(lyrics: &Value) -> Vec<Line> {
+    lyrics
+        .get("lines")
+        .and_then(Value::as_array)
+        .map(|lines| {
+            lines
+                .iter()
+                .filter_map(|line| {
+                    let start_ms = line.get("startTimeMs")?.as_str()?.parse::<u64>().ok()?;
+                    let words = line.get("words")?.as_str()?.to_string();
+                    Some(Line { start_ms, words })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Renders a Spotify `lyrics` payload in the requested format, returning the
+/// body alongside the content type it should be served with. Unsynced
+/// lyrics fall back to plain newline-joined text for `Lrc`/`Vtt` requests.
+pub fn render(lyrics: &Value, format: Format) -> (String, &'static str) {
+    if format == Format::Json {
+        return (lyrics.to_string(), Format::Json.content_type());
+    }
+
+    let sync_type = lyrics
+        .get("syncType")
+        .and_then(Value::as_str)
+        .unwrap_or("UNSYNCED");
+    let lines = parse_lines(lyrics);
+
+    if sync_type != "LINE_SYNCED" || lines.is_empty() {
+        let text = lines
+            .into_iter()
+            .map(|line| line.words)
+            .collect::<Vec<_>>()
+            .join("\n");
+        return (text, "text/plain; charset=utf-8");
+    }
+
+    match format {
+        Format::Lrc => (render_lrc(&lines), Format::Lrc.content_type()),
+        Format::Vtt => (render_vtt(&lines), Format::Vtt.content_type()),
+        Format::Json => unreachable!(),
+    }
+}
+
+fn render_lrc(lines: &[Line]) -> String {
+    lines
+        .iter()
+        .map(|line| {
+            let minutes = line.start_ms / 60_000;
+            let seconds = (line.start_ms / 1000) % 60;
+            let centiseconds = (line.start_ms % 1000) / 10;
+            format!(
+                "[{:02}:{:02}.{:02}]{}",
+                minutes, seconds, centiseconds, line.words
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn render_vtt(lines: &[Line]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+
+    for (i, line) in lines.iter().enumerate() {
+        let next_start_ms = lines
+            .get(i + 1)
+            .map(|next| next.start_ms)
+            .unwrap_or(line.start_ms + 4000);
+
+        out.push_str(&format!(
+            "{} --> {}\n{}\n\n",
+            format_vtt_timestamp(line.start_ms),
+            format_vtt_timestamp(next_start_ms),
+            line.words
+        ));
+    }
+
+    out
+}
+
+fn format_vtt_timestamp(ms: u64) -> String {
+    let hours = ms / 3_600_000;
+    let minutes = (ms / 60_000) % 60;
+    let seconds = (ms / 1000) % 60;
+    let millis = ms % 1000;
+    format!("{:02}:{:02}:{:02}.{:03}", hours, minutes, seconds, millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_format_prefers_query_over_accept() {
+        assert_eq!(parse_format(Some("vtt"), Some("application/json")), Format::Vtt);
+        assert_eq!(parse_format(Some("lrc"), None), Format::Lrc);
+        assert_eq!(parse_format(None, Some("text/vtt")), Format::Vtt);
+        assert_eq!(parse_format(None, Some("application/x-lrc")), Format::Lrc);
+        assert_eq!(parse_format(None, None), Format::Json);
+    }
+
+    #[test]
+    fn render_lrc_formats_timestamps() {
+        let lyrics = json!({
+            "syncType": "LINE_SYNCED",
+            "lines": [
+                {"startTimeMs": "0", "words": "first"},
+                {"startTimeMs": "61234", "words": "second"},
+            ]
+        });
+
+        let (body, content_type) = render(&lyrics, Format::Lrc);
+
+        assert_eq!(body, "[00:00.00]first\n[01:01.23]second");
+        assert_eq!(content_type, "text/plain; charset=utf-8");
+    }
+
+    #[test]
+    fn render_vtt_uses_next_line_start_and_pads_last_cue() {
+        let lyrics = json!({
+            "syncType": "LINE_SYNCED",
+            "lines": [
+                {"startTimeMs": "1000", "words": "first"},
+                {"startTimeMs": "4500", "words": "second"},
+            ]
+        });
+
+        let (body, content_type) = render(&lyrics, Format::Vtt);
+
+        assert_eq!(
+            body,
+            "WEBVTT\n\n00:00:01.000 --> 00:00:04.500\nfirst\n\n00:00:04.500 --> 00:00:08.500\nsecond\n\n"
+        );
+        assert_eq!(content_type, "text/vtt; charset=utf-8");
+    }
+
+    #[test]
+    fn unsynced_lyrics_fall_back_to_plain_text() {
+        let lyrics = json!({
+            "syncType": "UNSYNCED",
+            "lines": [
+                {"startTimeMs": "0", "words": "first"},
+                {"startTimeMs": "0", "words": "second"},
+            ]
+        });
+
+        let (body, content_type) = render(&lyrics, Format::Lrc);
+
+        assert_eq!(body, "first\nsecond");
+        assert_eq!(content_type, "text/plain; charset=utf-8");
+    }
+}
@@ -2,8 +2,9 @@ use std::collections::HashMap;
 
 use anyhow::{ensure, Result};
 use axum::{
-    extract::Path,
-    http::{HeaderMap, StatusCode},
+    extract::{Path, Query, Request},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
     response::{IntoResponse, Response},
     routing::get,
     Json, Router,
@@ -11,17 +12,67 @@ use axum::{
 use lazy_static::lazy_static;
 use listenfd::ListenFd;
 use paris::{error, info, log, warn};
-use rand::seq::SliceRandom;
 use serde_json::Value;
+use subtle::ConstantTimeEq;
 use tokio::{net::TcpListener, sync::Mutex};
+use tower_http::{
+    compression::CompressionLayer,
+    cors::{Any, CorsLayer},
+    trace::TraceLayer,
+};
 
+mod cache;
 mod constants;
+mod lyrics;
+mod oauth;
+mod pool;
+mod time;
+mod tokens;
 
 #[derive(serde::Deserialize)]
 struct Config {
     port: Option<u16>,
     api_keys: Option<Vec<String>>,
+    #[serde(default)]
     cookies: Vec<String>,
+    oauth: Option<OAuthConfig>,
+    cache_ttl_secs: Option<u64>,
+    #[cfg(feature = "redis-cache")]
+    redis_url: Option<String>,
+    internal_port: Option<u16>,
+    internal_secret: Option<String>,
+    #[serde(default = "default_tokens_file")]
+    tokens_file: String,
+    #[serde(default)]
+    allowed_origins: Vec<String>,
+}
+
+fn default_tokens_file() -> String {
+    "tokens.json".to_string()
+}
+
+#[derive(serde::Deserialize)]
+struct OAuthConfig {
+    client_id: String,
+    #[serde(default = "default_redirect_port")]
+    redirect_port: u16,
+    #[serde(default = "default_token_file")]
+    token_file: String,
+}
+
+fn default_redirect_port() -> u16 {
+    8898
+}
+
+fn default_token_file() -> String {
+    "oauth_tokens.json".to_string()
+}
+
+/// A single source of Spotify access, picked per request by `SpotifyClient`.
+#[derive(Debug, Clone)]
+enum Credential {
+    Cookie(String),
+    OAuth,
 }
 
 lazy_static! {
@@ -30,23 +81,48 @@ lazy_static! {
         &std::fs::read_to_string("config.toml").expect("Failed to read config.toml")
     )
     .expect("Failed to parse config.toml");
+    static ref TOKEN_STORE: tokens::TokenStore = tokens::TokenStore::load(&CONFIG.tokens_file);
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    tracing_subscriber::fmt::init();
+
     ensure!(
-        CONFIG.cookies.len() > 0,
-        "You must provide at least one sp_dc cookie"
+        CONFIG.cookies.len() > 0 || CONFIG.oauth.is_some(),
+        "You must provide at least one sp_dc cookie or an [oauth] configuration"
     );
 
     if CONFIG.api_keys.is_none() || CONFIG.api_keys.as_ref().unwrap().len() == 0 {
         warn!("No API key provided, this means anyone can use your API");
     }
 
+    if CONFIG.oauth.is_some() {
+        // Run the (possibly interactive) OAuth grant once up front, while
+        // nothing is being served yet, instead of lazily on first request:
+        // otherwise the first `/lyrics` call would hold the global `CLIENT`
+        // lock while blocking on a human clicking through the authorize URL,
+        // stalling every other request behind it.
+        CLIENT.lock().await.get_access_token(&Credential::OAuth).await?;
+    }
+
+    if let Some(internal_port) = CONFIG.internal_port {
+        if CONFIG.internal_secret.is_none() {
+            warn!(
+                "internal_port is set but no internal_secret is configured, this means anyone with network access to it can mint and revoke API keys"
+            );
+        }
+        tokio::spawn(serve_admin_api(internal_port));
+    }
+
     // build our application with a route
     let app = Router::new()
         .route("/", get(root))
-        .route("/lyrics/:track_id", get(lyrics));
+        .route("/lyrics/:track_id", get(lyrics))
+        .route("/health", get(health))
+        .layer(TraceLayer::new_for_http())
+        .layer(CompressionLayer::new())
+        .layer(cors_layer());
 
     let mut listenfd = ListenFd::from_env();
     let listener = match listenfd.take_tcp_listener(0).unwrap() {
@@ -68,8 +144,143 @@ async fn root() -> String {
     format!("{} v{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
 }
 
-async fn lyrics(headers: HeaderMap, Path(track_id): Path<String>) -> Result<Json<Value>, AppError> {
-    if let Some(api_keys) = &CONFIG.api_keys {
+/// Builds the CORS layer from `allowed_origins`: no origins configured means
+/// CORS stays off, `"*"` allows any origin, otherwise only the listed ones.
+fn cors_layer() -> CorsLayer {
+    if CONFIG.allowed_origins.is_empty() {
+        return CorsLayer::new();
+    }
+
+    if CONFIG.allowed_origins.iter().any(|origin| origin == "*") {
+        return CorsLayer::new()
+            .allow_origin(Any)
+            .allow_methods(Any)
+            .allow_headers(Any);
+    }
+
+    let origins = CONFIG
+        .allowed_origins
+        .iter()
+        .filter_map(|origin| origin.parse().ok())
+        .collect::<Vec<axum::http::HeaderValue>>();
+
+    // `/lyrics` requires an `Authorization` header whenever `api_keys` or
+    // `internal_port` is configured, which makes every cross-origin request
+    // non-simple: without these, the preflight response won't authorize the
+    // method or header and the browser blocks the real request regardless of
+    // `allow_origin`.
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods([axum::http::Method::GET])
+        .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE])
+}
+
+async fn health() -> Json<Vec<pool::CookieStatus>> {
+    Json(CLIENT.lock().await.cookie_pool.status())
+}
+
+/// Runs the internal admin API used to mint, list, and revoke API keys
+/// without restarting the proxy. Bound separately from the public router so
+/// it can be kept off a public interface.
+async fn serve_admin_api(internal_port: u16) {
+    let admin_app = Router::new()
+        .route(
+            "/tokens",
+            get(list_tokens)
+                .post(mint_token)
+                .delete(revoke_token),
+        )
+        .layer(middleware::from_fn(require_internal_secret));
+
+    let listener = TcpListener::bind(format!("127.0.0.1:{}", internal_port))
+        .await
+        .unwrap();
+
+    info!(
+        "Internal admin API listening on <b>{}</>",
+        listener.local_addr().unwrap()
+    );
+    axum::serve(listener, admin_app).await.unwrap();
+}
+
+/// Rejects admin API requests unless `internal_secret` is configured and the
+/// request carries it as a bearer token. With no `internal_secret` set, the
+/// admin API is left open (the startup warning covers that case).
+async fn require_internal_secret(
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if let Some(secret) = &CONFIG.internal_secret {
+        // Compare in constant time: this guards key minting/revocation, so a
+        // timing side-channel on `==` shouldn't be able to narrow down the
+        // secret byte by byte.
+        let authorized = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map_or(false, |token| {
+                token.as_bytes().ct_eq(secret.as_bytes()).into()
+            });
+
+        if !authorized {
+            return Err(anyhow::anyhow!("Invalid internal secret").into());
+        }
+    }
+
+    Ok(next.run(request).await)
+}
+
+#[derive(serde::Deserialize)]
+struct MintTokenQuery {
+    expires_in_secs: Option<u64>,
+}
+
+async fn mint_token(
+    Query(query): Query<MintTokenQuery>,
+) -> Result<Json<tokens::IssuedToken>, AppError> {
+    // `TokenStore::mint` does a synchronous `fs::write` to persist the token
+    // store; run it on the blocking pool instead of the async worker thread.
+    let token = tokio::task::spawn_blocking(move || TOKEN_STORE.mint(query.expires_in_secs))
+        .await??;
+
+    Ok(Json(token))
+}
+
+async fn list_tokens() -> Json<Vec<tokens::IssuedToken>> {
+    Json(TOKEN_STORE.list())
+}
+
+#[derive(serde::Deserialize)]
+struct RevokeTokenQuery {
+    key: String,
+}
+
+async fn revoke_token(
+    Query(query): Query<RevokeTokenQuery>,
+) -> Result<StatusCode, AppError> {
+    let removed = tokio::task::spawn_blocking(move || TOKEN_STORE.revoke(&query.key)).await??;
+
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Ok(StatusCode::NOT_FOUND)
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct LyricsQuery {
+    format: Option<String>,
+}
+
+async fn lyrics(
+    headers: HeaderMap,
+    Path(track_id): Path<String>,
+    Query(query): Query<LyricsQuery>,
+) -> Result<Response, AppError> {
+    let keys_configured = CONFIG.api_keys.as_ref().map_or(false, |keys| !keys.is_empty());
+
+    if keys_configured || CONFIG.internal_port.is_some() {
         let authorization = headers
             .get("authorization")
             .ok_or_else(|| anyhow::anyhow!("Authorization header not found"))?;
@@ -82,19 +293,44 @@ async fn lyrics(headers: HeaderMap, Path(track_id): Path<String>) -> Result<Json
 
         log!("Authorization: {}", authorization);
 
-        if !api_keys.contains(&authorization.to_string()) {
+        let authorized = CONFIG
+            .api_keys
+            .as_ref()
+            .map_or(false, |keys| keys.contains(&authorization.to_string()))
+            || TOKEN_STORE.is_valid(authorization);
+
+        if !authorized {
             return Err(anyhow::anyhow!("Invalid API key").into());
         }
     }
 
-    let lyrics = CLIENT.lock().await.get_lyrics(&track_id).await?;
+    let (lyrics, cache_hit, ttl_remaining_secs) = CLIENT.lock().await.get_lyrics(&track_id).await?;
+
+    let format = lyrics::parse_format(
+        query.format.as_deref(),
+        headers.get(header::ACCEPT).and_then(|value| value.to_str().ok()),
+    );
+    let (body, content_type) = lyrics::render(&lyrics, format);
 
-    Ok(Json(lyrics))
+    let response_headers = [
+        (header::CONTENT_TYPE, content_type.to_string()),
+        (
+            header::CACHE_CONTROL,
+            format!("max-age={}", ttl_remaining_secs),
+        ),
+        (
+            axum::http::HeaderName::from_static("x-cache"),
+            if cache_hit { "HIT" } else { "MISS" }.to_string(),
+        ),
+    ];
+
+    Ok((response_headers, body).into_response())
 }
 
-#[derive(Debug)]
 struct SpotifyClient {
     access_tokens: HashMap<String, AccessToken>,
+    cookie_pool: pool::CookiePool,
+    cache: cache::Cache,
     user_agent: String,
 }
 
@@ -102,75 +338,212 @@ struct SpotifyClient {
 struct AccessToken {
     token: String,
     expires_at: u64,
+    refresh_token: Option<String>,
 }
 
 impl SpotifyClient {
     fn new() -> Self {
         Self {
             access_tokens: HashMap::new(),
+            cookie_pool: pool::CookiePool::new(CONFIG.cookies.clone()),
+            cache: Self::build_cache(),
             user_agent: constants::USER_AGENT.to_string(),
         }
     }
 
-    async fn get_access_token(&mut self, cookie: String) -> Result<(), anyhow::Error> {
-        let client = reqwest::Client::new();
-
-        let response = client
-            .get(constants::TOKEN_URL)
-            .header("App-platform", "WebPlayer")
-            .header("Cookie", format!("sp_dc={}", cookie))
-            .header("User-Agent", &self.user_agent)
-            .header("Content-Type", "text/html")
-            .send()
-            .await?;
+    #[cfg(feature = "redis-cache")]
+    fn build_cache() -> cache::Cache {
+        match &CONFIG.redis_url {
+            Some(redis_url) => cache::Cache::redis(redis_url).unwrap_or_else(|err| {
+                warn!("Failed to connect to Redis cache, falling back to in-memory: {}", err);
+                cache::Cache::memory()
+            }),
+            None => cache::Cache::memory(),
+        }
+    }
 
-        // log!("Response: {:?}", response);
+    #[cfg(not(feature = "redis-cache"))]
+    fn build_cache() -> cache::Cache {
+        cache::Cache::memory()
+    }
 
-        let parsed = serde_json::from_str::<Value>(&response.text().await?)?;
+    /// Mints or refreshes the access token for `credential`, storing it under
+    /// its pool key in `access_tokens`.
+    async fn get_access_token(&mut self, credential: &Credential) -> Result<(), anyhow::Error> {
+        match credential {
+            Credential::Cookie(cookie) => {
+                let client = reqwest::Client::new();
+
+                let response = client
+                    .get(constants::TOKEN_URL)
+                    .header("App-platform", "WebPlayer")
+                    .header("Cookie", format!("sp_dc={}", cookie))
+                    .header("User-Agent", &self.user_agent)
+                    .header("Content-Type", "text/html")
+                    .send()
+                    .await?;
+
+                let status = response.status().as_u16();
+                let body = response.text().await?;
+
+                // A dead/rate-limited cookie fails right here, not at the
+                // lyrics call, so this needs the same typed `SpotifyApiError`
+                // as `fetch_lyrics` for the pool's failover loop to catch it.
+                if status != 200 {
+                    error!("Response: {} {}", status, body);
+                    return Err(SpotifyApiError { status }.into());
+                }
 
-        // log!("Parsed: {:?}", parsed);
+                let parsed = serde_json::from_str::<Value>(&body)?;
 
-        self.access_tokens.insert(
-            cookie,
-            AccessToken {
-                token: parsed
+                let token = parsed
                     .get("accessToken")
-                    .unwrap()
-                    .as_str()
-                    .unwrap()
-                    .to_string(),
-                expires_at: parsed
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| anyhow::anyhow!("Token response missing `accessToken`"))?
+                    .to_string();
+                let expires_at = parsed
                     .get("accessTokenExpirationTimestampMs")
-                    .unwrap()
-                    .as_u64()
-                    .unwrap(),
-            },
-        );
+                    .and_then(Value::as_u64)
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Token response missing `accessTokenExpirationTimestampMs`"
+                        )
+                    })?;
+
+                self.access_tokens.insert(
+                    cookie.clone(),
+                    AccessToken {
+                        token,
+                        expires_at,
+                        refresh_token: None,
+                    },
+                );
+            }
+            Credential::OAuth => {
+                let oauth_config = CONFIG
+                    .oauth
+                    .as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("No [oauth] configuration provided"))?;
+
+                let existing_refresh_token = self
+                    .access_tokens
+                    .get(constants::OAUTH_CREDENTIAL_KEY)
+                    .and_then(|token| token.refresh_token.clone())
+                    .or_else(|| {
+                        oauth::load_persisted(&oauth_config.token_file)
+                            .map(|tokens| tokens.refresh_token)
+                    });
+
+                let tokens = match existing_refresh_token {
+                    Some(refresh_token) => {
+                        oauth::refresh(&oauth_config.client_id, &refresh_token).await?
+                    }
+                    None => {
+                        oauth::authorize(&oauth_config.client_id, oauth_config.redirect_port)
+                            .await?
+                    }
+                };
+
+                oauth::save_persisted(&oauth_config.token_file, &tokens)?;
+
+                self.access_tokens.insert(
+                    constants::OAUTH_CREDENTIAL_KEY.to_string(),
+                    AccessToken {
+                        token: tokens.access_token,
+                        expires_at: tokens.expires_at,
+                        refresh_token: Some(tokens.refresh_token),
+                    },
+                );
+            }
+        }
 
         Ok(())
     }
 
-    async fn get_lyrics(&mut self, track_id: &str) -> Result<Value, anyhow::Error> {
-        let cookie = CONFIG
-            .cookies
-            .choose(&mut rand::thread_rng())
-            .ok_or_else(|| anyhow::anyhow!("No cookies provided"))?;
+    /// Returns the lyrics for `track_id`, whether they came from the cache,
+    /// and how many seconds they're still fresh for: the full TTL on a miss,
+    /// or whatever's left of it on a cache hit.
+    async fn get_lyrics(&mut self, track_id: &str) -> Result<(Value, bool, u64), anyhow::Error> {
+        if let Some((cached, ttl_remaining_secs)) = self.cache.get(track_id).await {
+            return Ok((cached, true, ttl_remaining_secs));
+        }
+
+        let ttl_secs = CONFIG.cache_ttl_secs.unwrap_or(constants::DEFAULT_CACHE_TTL_SECS);
+        let lyrics = self.fetch_with_failover(track_id).await?;
+        self.cache.set(track_id, lyrics.clone(), ttl_secs).await;
 
-        let access_token = self.access_tokens.get(cookie);
+        Ok((lyrics, false, ttl_secs))
+    }
+
+    async fn fetch_with_failover(&mut self, track_id: &str) -> Result<Value, anyhow::Error> {
+        if CONFIG.oauth.is_some() {
+            return self.fetch_lyrics(track_id, &Credential::OAuth).await;
+        }
+
+        let mut excluded = Vec::new();
+
+        loop {
+            let cookie = self
+                .cookie_pool
+                .select(&excluded)
+                .ok_or_else(|| anyhow::anyhow!("No healthy cookies available"))?
+                .clone();
+
+            match self
+                .fetch_lyrics(track_id, &Credential::Cookie(cookie.clone()))
+                .await
+            {
+                Ok(lyrics) => {
+                    self.cookie_pool.record_success(&cookie);
+                    return Ok(lyrics);
+                }
+                Err(err) => {
+                    // Transport-level failures (timeouts, resets, DNS
+                    // errors) never downcast to `SpotifyApiError`; treat
+                    // them as the generic, retryable status `0` so a
+                    // transient blip on one cookie doesn't abort the whole
+                    // request.
+                    let status = err
+                        .downcast_ref::<SpotifyApiError>()
+                        .map_or(0, |api_err| api_err.status);
+
+                    excluded.push(cookie.clone());
+                    self.cookie_pool.record_failure(&cookie, status);
+
+                    if !pool::is_retryable(status) || self.cookie_pool.select(&excluded).is_none()
+                    {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn fetch_lyrics(
+        &mut self,
+        track_id: &str,
+        credential: &Credential,
+    ) -> Result<Value, anyhow::Error> {
+        let key = match credential {
+            Credential::Cookie(cookie) => cookie.as_str(),
+            Credential::OAuth => constants::OAUTH_CREDENTIAL_KEY,
+        };
+
+        let access_token = self.access_tokens.get(key);
 
         let access_token = match access_token {
             Some(access_token) => {
                 match access_token.expires_at > chrono::Utc::now().timestamp_millis() as u64 {
                     true => access_token,
                     false => {
-                        self.get_access_token(cookie.to_string()).await?;
-                        self.access_tokens.get(cookie).unwrap()
+                        self.get_access_token(credential).await?;
+                        self.access_tokens.get(key).unwrap()
                     }
                 }
             }
             None => {
-                self.get_access_token(cookie.to_string()).await?;
-                self.access_tokens.get(cookie).unwrap()
+                self.get_access_token(credential).await?;
+                self.access_tokens.get(key).unwrap()
             }
         };
 
@@ -191,7 +564,9 @@ impl SpotifyClient {
             .send()
             .await?;
 
-        match response.status().as_u16() {
+        let status = response.status().as_u16();
+
+        match status {
             200 => {
                 let parsed = response.json::<Value>().await?;
                 Ok(parsed.get("lyrics").unwrap().clone())
@@ -199,12 +574,28 @@ impl SpotifyClient {
 
             _ => {
                 error!("Response: {:?}", response);
-                Err(anyhow::anyhow!("Something went wrong"))
+                Err(SpotifyApiError { status }.into())
             }
         }
     }
 }
 
+/// A non-200 response from the Spotify API, carrying the status code so
+/// callers (like the cookie pool's failover loop) can decide whether it's
+/// worth retrying.
+#[derive(Debug)]
+struct SpotifyApiError {
+    status: u16,
+}
+
+impl std::fmt::Display for SpotifyApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Spotify API returned status {}", self.status)
+    }
+}
+
+impl std::error::Error for SpotifyApiError {}
+
 // Make our own error that wraps `anyhow::Error`.
 struct AppError(anyhow::Error);
 
@@ -0,0 +1,187 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use paris::info;
+use rand::RngCore;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+use crate::constants;
+
+/// Tokens obtained through the OAuth authorization-code (PKCE) flow.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct OAuthTokens {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_at: u64,
+}
+
+/// Loads previously persisted tokens from `path`, if any exist.
+pub fn load_persisted(path: &str) -> Option<OAuthTokens> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Persists `tokens` to `path` so the proxy can resume without a fresh
+/// interactive authorization on restart.
+pub fn save_persisted(path: &str, tokens: &OAuthTokens) -> Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(tokens)?)?;
+    Ok(())
+}
+
+/// Runs a one-time interactive authorization-code flow: opens a local
+/// callback listener, prints the authorize URL for the user to visit, and
+/// exchanges the returned code for a token pair.
+pub async fn authorize(client_id: &str, redirect_port: u16) -> Result<OAuthTokens> {
+    let (verifier, challenge) = generate_pkce();
+    let redirect_uri = format!("http://127.0.0.1:{}/callback", redirect_port);
+
+    let authorize_url = format!(
+        "{}?response_type=code&client_id={}&redirect_uri={}&scope={}&code_challenge_method=S256&code_challenge={}",
+        constants::OAUTH_AUTHORIZE_URL,
+        client_id,
+        urlencoding::encode(&redirect_uri),
+        urlencoding::encode(constants::OAUTH_SCOPES),
+        challenge,
+    );
+
+    info!("Open this URL in your browser to authorize the proxy:");
+    info!("<b>{}</>", authorize_url);
+
+    let listener = TcpListener::bind(("127.0.0.1", redirect_port)).await?;
+    let code = receive_callback_code(listener).await?;
+
+    exchange_code(client_id, &redirect_uri, &code, &verifier).await
+}
+
+/// Exchanges a stored refresh token for a fresh access token.
+pub async fn refresh(client_id: &str, refresh_token: &str) -> Result<OAuthTokens> {
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(constants::OAUTH_TOKEN_URL)
+        .form(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+            ("client_id", client_id),
+        ])
+        .send()
+        .await?;
+
+    parse_token_response(response, Some(refresh_token)).await
+}
+
+fn generate_pkce() -> (String, String) {
+    let mut verifier_bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut verifier_bytes);
+    let verifier = URL_SAFE_NO_PAD.encode(verifier_bytes);
+
+    let mut hasher = Sha256::new();
+    hasher.update(verifier.as_bytes());
+    let challenge = URL_SAFE_NO_PAD.encode(hasher.finalize());
+
+    (verifier, challenge)
+}
+
+async fn receive_callback_code(listener: TcpListener) -> Result<String> {
+    let (mut stream, _) = listener.accept().await?;
+
+    let mut buf = [0u8; 2048];
+    let n = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..n]);
+
+    let request_line = request
+        .lines()
+        .next()
+        .ok_or_else(|| anyhow!("Empty callback request"))?;
+    let path = request_line
+        .split_whitespace()
+        .nth(1)
+        .ok_or_else(|| anyhow!("Malformed callback request"))?;
+    let query = path
+        .splitn(2, '?')
+        .nth(1)
+        .ok_or_else(|| anyhow!("Callback request missing query string"))?;
+
+    let code = query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("code="))
+        .ok_or_else(|| anyhow!("Callback request missing `code` parameter"))?
+        .to_string();
+
+    let body = "Authorization complete, you can close this tab.";
+    let response = format!(
+        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nContent-Type: text/plain\r\n\r\n{}",
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+
+    Ok(code)
+}
+
+async fn exchange_code(
+    client_id: &str,
+    redirect_uri: &str,
+    code: &str,
+    verifier: &str,
+) -> Result<OAuthTokens> {
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(constants::OAUTH_TOKEN_URL)
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+            ("client_id", client_id),
+            ("code_verifier", verifier),
+        ])
+        .send()
+        .await?;
+
+    parse_token_response(response, None).await
+}
+
+async fn parse_token_response(
+    response: reqwest::Response,
+    fallback_refresh_token: Option<&str>,
+) -> Result<OAuthTokens> {
+    let parsed = response.json::<Value>().await?;
+
+    let access_token = parsed
+        .get("access_token")
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("Token response missing `access_token`"))?
+        .to_string();
+
+    let refresh_token = parsed
+        .get("refresh_token")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .or_else(|| fallback_refresh_token.map(str::to_string))
+        .ok_or_else(|| anyhow!("Token response missing `refresh_token`"))?;
+
+    let expires_in = parsed
+        .get("expires_in")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| anyhow!("Token response missing `expires_in`"))?;
+
+    Ok(OAuthTokens {
+        access_token,
+        refresh_token,
+        expires_at: now_ms() + expires_in * 1000,
+    })
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
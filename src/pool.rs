@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use crate::time::now_secs;
+
+/// Health tracking for a single cookie in the pool.
+#[derive(Debug, Clone, Default)]
+struct CookieHealth {
+    consecutive_failures: u32,
+    last_used_at: Option<u64>,
+    cooldown_until: Option<u64>,
+}
+
+impl CookieHealth {
+    fn is_healthy(&self, now: u64) -> bool {
+        self.cooldown_until.map_or(true, |until| now >= until)
+    }
+}
+
+/// Status of a single cookie, safe to expose over `/health`.
+#[derive(Debug, serde::Serialize)]
+pub struct CookieStatus {
+    pub cookie: String,
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+    pub cooldown_until: Option<u64>,
+}
+
+/// A managed pool of `sp_dc` cookies that tracks per-cookie health and picks
+/// the healthiest one available instead of a blind random choice.
+#[derive(Debug)]
+pub struct CookiePool {
+    cookies: Vec<String>,
+    health: HashMap<String, CookieHealth>,
+}
+
+impl CookiePool {
+    pub fn new(cookies: Vec<String>) -> Self {
+        let health = cookies
+            .iter()
+            .cloned()
+            .map(|cookie| (cookie, CookieHealth::default()))
+            .collect();
+
+        Self { cookies, health }
+    }
+
+    /// Picks the healthiest cookie not already in `exclude`: not cooling
+    /// down, fewest consecutive failures, then least-recently used.
+    pub fn select(&self, exclude: &[String]) -> Option<&String> {
+        let now = now_secs();
+
+        self.cookies
+            .iter()
+            .filter(|cookie| !exclude.contains(cookie))
+            .filter(|cookie| {
+                self.health
+                    .get(cookie.as_str())
+                    .map_or(true, |health| health.is_healthy(now))
+            })
+            .min_by_key(|cookie| {
+                let health = self.health.get(cookie.as_str()).cloned().unwrap_or_default();
+                (health.consecutive_failures, health.last_used_at.unwrap_or(0))
+            })
+    }
+
+    pub fn record_success(&mut self, cookie: &str) {
+        let health = self.health.entry(cookie.to_string()).or_default();
+        health.consecutive_failures = 0;
+        health.cooldown_until = None;
+        health.last_used_at = Some(now_secs());
+    }
+
+    /// Records a failed request. Retryable statuses (401/403/429, plus `0`
+    /// for a transport-level failure with no response at all) put the
+    /// cookie on a short cooldown so the pool stops selecting it for a
+    /// while.
+    pub fn record_failure(&mut self, cookie: &str, status: u16) {
+        let health = self.health.entry(cookie.to_string()).or_default();
+        health.consecutive_failures += 1;
+        health.last_used_at = Some(now_secs());
+
+        let cooldown_secs = match status {
+            429 => 300,
+            401 | 403 => 60,
+            0 => 15,
+            _ => return,
+        };
+        health.cooldown_until = Some(now_secs() + cooldown_secs);
+    }
+
+    pub fn status(&self) -> Vec<CookieStatus> {
+        let now = now_secs();
+
+        self.cookies
+            .iter()
+            .map(|cookie| {
+                let health = self.health.get(cookie.as_str()).cloned().unwrap_or_default();
+                CookieStatus {
+                    cookie: mask(cookie),
+                    healthy: health.is_healthy(now),
+                    consecutive_failures: health.consecutive_failures,
+                    cooldown_until: health.cooldown_until,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Is this status worth retrying with a different cookie? `0` stands for a
+/// transport-level failure (no response at all), which is as retryable as a
+/// rate limit.
+pub fn is_retryable(status: u16) -> bool {
+    matches!(status, 401 | 403 | 429 | 0)
+}
+
+fn mask(cookie: &str) -> String {
+    if cookie.len() <= 8 {
+        return "*".repeat(cookie.len());
+    }
+    format!("{}…{}", &cookie[..4], &cookie[cookie.len() - 4..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_skips_excluded_cookies() {
+        let pool = CookiePool::new(vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(pool.select(&["a".to_string()]).cloned(), Some("b".to_string()));
+    }
+
+    #[test]
+    fn select_skips_cookies_on_cooldown() {
+        let mut pool = CookiePool::new(vec!["a".to_string(), "b".to_string()]);
+        pool.record_failure("a", 429);
+        assert_eq!(pool.select(&[]).cloned(), Some("b".to_string()));
+    }
+
+    #[test]
+    fn select_prefers_fewer_consecutive_failures() {
+        let mut pool = CookiePool::new(vec!["a".to_string(), "b".to_string()]);
+        pool.record_failure("a", 500);
+        assert_eq!(pool.select(&[]).cloned(), Some("b".to_string()));
+    }
+
+    #[test]
+    fn record_success_clears_cooldown_and_failures() {
+        let mut pool = CookiePool::new(vec!["a".to_string()]);
+        pool.record_failure("a", 429);
+        pool.record_success("a");
+        assert_eq!(pool.select(&[]).cloned(), Some("a".to_string()));
+    }
+
+    #[test]
+    fn transport_and_rate_limit_failures_are_retryable_but_server_errors_are_not() {
+        assert!(is_retryable(0));
+        assert!(is_retryable(429));
+        assert!(is_retryable(401));
+        assert!(!is_retryable(500));
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use rand::RngCore;
+
+use crate::time::now_secs;
+
+/// An API key minted through the internal admin API, as opposed to one of
+/// the static keys in `config.toml`.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct IssuedToken {
+    pub key: String,
+    pub issued_at: u64,
+    pub expires_at: Option<u64>,
+}
+
+impl IssuedToken {
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.map_or(false, |expires_at| now >= expires_at)
+    }
+}
+
+/// Issued tokens, persisted to `path` so they survive a restart.
+pub struct TokenStore {
+    path: String,
+    tokens: Mutex<HashMap<String, IssuedToken>>,
+}
+
+impl TokenStore {
+    pub fn load(path: &str) -> Self {
+        let tokens = std::fs::read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Vec<IssuedToken>>(&contents).ok())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|token| (token.key.clone(), token))
+            .collect();
+
+        Self {
+            path: path.to_string(),
+            tokens: Mutex::new(tokens),
+        }
+    }
+
+    pub fn mint(&self, ttl_secs: Option<u64>) -> Result<IssuedToken> {
+        let now = now_secs();
+        let token = IssuedToken {
+            key: generate_key(),
+            issued_at: now,
+            expires_at: ttl_secs.map(|ttl| now + ttl),
+        };
+
+        let mut tokens = self.tokens.lock().unwrap();
+        tokens.insert(token.key.clone(), token.clone());
+        self.persist(&tokens)?;
+
+        Ok(token)
+    }
+
+    pub fn revoke(&self, key: &str) -> Result<bool> {
+        let mut tokens = self.tokens.lock().unwrap();
+        let removed = tokens.remove(key).is_some();
+
+        if removed {
+            self.persist(&tokens)?;
+        }
+
+        Ok(removed)
+    }
+
+    pub fn list(&self) -> Vec<IssuedToken> {
+        self.tokens.lock().unwrap().values().cloned().collect()
+    }
+
+    pub fn is_valid(&self, key: &str) -> bool {
+        match self.tokens.lock().unwrap().get(key) {
+            Some(token) => !token.is_expired(now_secs()),
+            None => false,
+        }
+    }
+
+    fn persist(&self, tokens: &HashMap<String, IssuedToken>) -> Result<()> {
+        let serialized = serde_json::to_string_pretty(&tokens.values().collect::<Vec<_>>())?;
+        std::fs::write(&self.path, serialized)?;
+        Ok(())
+    }
+}
+
+fn generate_key() -> String {
+    let mut bytes = [0u8; 24];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}